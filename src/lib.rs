@@ -1,12 +1,19 @@
+use arc_swap::ArcSwap;
 use rfc7239::{parse, Forwarded, NodeIdentifier, NodeName};
 use std::borrow::Cow;
 use std::convert::Infallible;
 use std::iter::once;
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
+use std::sync::Arc;
 use warp::filters::addr::remote;
 use warp::Filter;
 
+mod trusted;
+
+pub use arc_swap;
+pub use trusted::TrustedProxies;
+
 /// Creates a `Filter` that provides the "real ip" of the connected client.
 ///
 /// This uses the "x-forwarded-for" or "x-real-ip" headers set by reverse proxies.
@@ -15,6 +22,9 @@ use warp::Filter;
 /// Note that if multiple forwarded-for addresses are present, which can be the case when using nested reverse proxies,
 /// all proxies in the chain have to be within the list of trusted proxies.
 ///
+/// Trusted proxies can be a plain list of ip addresses, or, since proxy fleets usually live
+/// behind whole subnets, CIDR networks such as `10.0.0.0/8` or `fd00::/8` (see [`TrustedProxies`]).
+///
 /// ## Example
 ///
 /// ```no_run
@@ -22,58 +32,259 @@ use warp::Filter;
 /// use warp_real_ip::real_ip;
 /// use std::net::IpAddr;
 ///
-/// let proxy_addr = [127, 10, 0, 1].into();
+/// let proxy_addr: IpAddr = [127, 10, 0, 1].into();
 /// warp::any()
 ///     .and(real_ip(vec![proxy_addr]))
 ///     .map(|addr: Option<IpAddr>| format!("Hello {}", addr.unwrap()));
 /// ```
 pub fn real_ip(
-    trusted_proxies: Vec<IpAddr>,
+    trusted_proxies: impl Into<TrustedProxies>,
 ) -> impl Filter<Extract = (Option<IpAddr>,), Error = Infallible> + Clone {
+    let trusted_proxies = trusted_proxies.into();
     remote().and(get_forwarded_for()).map(
         move |addr: Option<SocketAddr>, forwarded_for: Vec<IpAddr>| {
-            addr.map(|addr| {
-                let hops = forwarded_for.iter().copied().chain(once(addr.ip()));
-                for hop in hops.rev() {
-                    if !trusted_proxies.contains(&hop) {
-                        return hop;
-                    }
-                }
+            find_real_ip(addr, &forwarded_for, &trusted_proxies)
+        },
+    )
+}
 
-                // all hops were trusted, return the last one
-                forwarded_for.first().copied().unwrap_or_else(|| addr.ip())
-            })
+/// Like [`real_ip`], but reads the trusted proxy set from `trusted_proxies` on every request
+/// instead of capturing it by value.
+///
+/// This allows a running server to atomically swap in a new trusted proxy set, for example after
+/// reloading a config file, without rebuilding the warp filter graph. In-flight requests keep
+/// using a consistent snapshot of the set.
+///
+/// `arc_swap` is re-exported as [`arc_swap`](crate::arc_swap) so callers don't need to add it as
+/// a separate dependency and risk a version mismatch with the `ArcSwap` used here.
+///
+/// ## Example
+///
+/// ```no_run
+/// use warp::Filter;
+/// use warp_real_ip::real_ip_shared;
+/// use warp_real_ip::arc_swap::ArcSwap;
+/// use std::net::IpAddr;
+/// use std::sync::Arc;
+///
+/// let proxy_addr: IpAddr = [127, 10, 0, 1].into();
+/// let trusted: Arc<ArcSwap<_>> = Arc::new(ArcSwap::from_pointee(vec![proxy_addr].into()));
+/// warp::any()
+///     .and(real_ip_shared(trusted))
+///     .map(|addr: Option<IpAddr>| format!("Hello {}", addr.unwrap()));
+/// ```
+pub fn real_ip_shared(
+    trusted_proxies: Arc<ArcSwap<TrustedProxies>>,
+) -> impl Filter<Extract = (Option<IpAddr>,), Error = Infallible> + Clone {
+    remote().and(get_forwarded_for()).map(
+        move |addr: Option<SocketAddr>, forwarded_for: Vec<IpAddr>| {
+            find_real_ip(addr, &forwarded_for, &trusted_proxies.load())
         },
     )
 }
 
-/// Creates a `Filter` that extracts the ip addresses from the the "forwarded for" chain
-pub fn get_forwarded_for() -> impl Filter<Extract = (Vec<IpAddr>,), Error = Infallible> + Clone {
+/// Walks the forwarded-for chain from the rear, peeling off trusted hops, and returns the first
+/// untrusted one. Shared between [`real_ip`], [`real_ip_shared`] and [`real_ip_info`].
+fn find_real_ip(
+    addr: Option<SocketAddr>,
+    forwarded_for: &[IpAddr],
+    trusted_proxies: &TrustedProxies,
+) -> Option<IpAddr> {
+    addr.map(|addr| {
+        let hops = forwarded_for.iter().copied().chain(once(addr.ip()));
+        for hop in hops.rev() {
+            if !trusted_proxies.contains(&hop) {
+                return hop;
+            }
+        }
+
+        // all hops were trusted, return the last one
+        forwarded_for.first().copied().unwrap_or_else(|| addr.ip())
+    })
+}
+
+/// Forwarding metadata about a request, as reported by a trusted reverse proxy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardInfo {
+    /// The real client ip, trust-walked the same way as [`real_ip`].
+    pub client: Option<IpAddr>,
+    /// The protocol (`http`/`https`) the client originally connected with.
+    pub proto: Option<String>,
+    /// The `Host` the client originally connected to.
+    pub host: Option<String>,
+    /// The proxy that identified itself as having handled the request, if known.
+    pub by: Option<IpAddr>,
+    /// The port from the client's `for=` node, or else from `X-Forwarded-Port`, if known.
+    pub port: Option<u16>,
+}
+
+/// Creates a `Filter` that provides the full forwarding metadata of a request: the real client
+/// ip (trust-walked exactly like [`real_ip`]), plus the originally requested protocol and host,
+/// the client's port, and the proxy that identifies itself as `by=`.
+///
+/// This is parsed from the `Forwarded` header (`proto=`, `host=`, `by=` and the `:port` suffix on
+/// a `for=` node), falling back to `X-Forwarded-Proto`/`X-Forwarded-Host`/`X-Forwarded-Port` for
+/// each field the `Forwarded` header does not carry. As in [`real_ip`], an `x-forwarded-for` or
+/// `x-real-ip` chain takes precedence over the `for=` nodes; its entries carry no port.
+pub fn real_ip_info(
+    trusted_proxies: impl Into<TrustedProxies>,
+) -> impl Filter<Extract = (ForwardInfo,), Error = Infallible> + Clone {
+    let trusted_proxies = trusted_proxies.into();
+    remote()
+        .and(get_forward_data())
+        .map(move |addr: Option<SocketAddr>, data: ForwardData| {
+            let forwarded_ips: Vec<IpAddr> = data.forwarded_for.iter().map(|(ip, _)| *ip).collect();
+            let client = find_real_ip(addr, &forwarded_ips, &trusted_proxies);
+            let port = client
+                .and_then(|client| {
+                    data.forwarded_for
+                        .iter()
+                        .find(|(ip, _)| *ip == client)
+                        .and_then(|(_, port)| *port)
+                })
+                .or(data.port);
+
+            ForwardInfo {
+                client,
+                proto: data.proto,
+                host: data.host,
+                by: data.by,
+                port,
+            }
+        })
+}
+
+/// The forwarding metadata extracted from request headers, before the trust-walk is applied.
+#[derive(Debug, Clone, Default)]
+struct ForwardData {
+    forwarded_for: Vec<(IpAddr, Option<u16>)>,
+    proto: Option<String>,
+    host: Option<String>,
+    by: Option<IpAddr>,
+    port: Option<u16>,
+}
+
+/// Creates a `Filter` that extracts the full forwarding metadata from the `Forwarded` header,
+/// falling back to the legacy `X-Forwarded-*` headers for fields it didn't find.
+fn get_forward_data() -> impl Filter<Extract = (ForwardData,), Error = Infallible> + Clone {
+    optional_header("forwarded")
+        .and(get_legacy_forwarded_for())
+        .and(optional_header("x-forwarded-proto"))
+        .and(optional_header("x-forwarded-host"))
+        .and(optional_header("x-forwarded-port"))
+        .map(
+            |forwarded: Option<String>,
+             legacy_forwarded_for: Option<Vec<IpAddr>>,
+             legacy_proto: Option<String>,
+             legacy_host: Option<String>,
+             legacy_port: Option<String>| {
+                let mut data = forwarded
+                    .as_deref()
+                    .map(parse_forwarded)
+                    .unwrap_or_default();
+
+                // Use the same header priority as `get_forwarded_for` (and thus `real_ip`) for
+                // the trust-walked chain: x-forwarded-for or x-real-ip win over the `Forwarded`
+                // header's `for=` nodes, which `data.forwarded_for` already holds as a fallback.
+                if let Some(legacy_forwarded_for) = legacy_forwarded_for {
+                    data.forwarded_for = legacy_forwarded_for
+                        .into_iter()
+                        .map(|ip| (ip, None))
+                        .collect();
+                }
+                data.proto = data.proto.or(legacy_proto);
+                data.host = data.host.or(legacy_host);
+                data.port = data.port.or(legacy_port.and_then(|port| port.parse().ok()));
+
+                data
+            },
+        )
+}
+
+/// Like `warp::header::optional`, but never rejects on a malformed header, treating it the same
+/// as a missing one.
+fn optional_header(
+    name: &'static str,
+) -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
+    warp::header(name)
+        .map(Some)
+        .or(warp::any().map(|| None))
+        .unify()
+}
+
+/// Parses the `Forwarded` header, accumulating every `for=` node (needed for the trust-walk)
+/// while keeping the last seen `by=`/`host=`/`proto=`, as those are set by the proxy closest to
+/// us.
+fn parse_forwarded(header: &str) -> ForwardData {
+    let mut data = ForwardData::default();
+    for forwarded in parse(header).filter_map(Result::ok) {
+        if let Some(NodeIdentifier {
+            name: NodeName::Ip(ip),
+            port,
+        }) = forwarded.forwarded_for
+        {
+            data.forwarded_for.push((ip, port));
+        }
+        if let Some(NodeIdentifier {
+            name: NodeName::Ip(ip),
+            ..
+        }) = forwarded.forwarded_by
+        {
+            data.by = Some(ip);
+        }
+        if let Some(host) = forwarded.host {
+            data.host = Some(host.to_string());
+        }
+        if let Some(proto) = forwarded.protocol {
+            data.proto = Some(proto.to_string());
+        }
+    }
+    data
+}
+
+/// Extracts the forwarded-for chain from the legacy `x-forwarded-for`/`x-real-ip` headers only,
+/// without falling back to the `Forwarded` header. `None` means neither header yielded a chain:
+/// an `x-forwarded-for` header that fails to parse counts as absent, while an unparsable
+/// `x-real-ip` gives `Some` of an empty list.
+fn get_legacy_forwarded_for(
+) -> impl Filter<Extract = (Option<Vec<IpAddr>>,), Error = Infallible> + Clone {
     warp::header("x-forwarded-for")
-        .map(|list: CommaSeparated<IpAddr>| list.into_inner())
+        .map(|list: CommaSeparated<IpAddr>| Some(list.into_inner()))
         .or(warp::header("x-real-ip").map(|ip: String| {
-            IpAddr::from_str(maybe_bracketed(&maybe_quoted(&ip)))
-                .map_or_else(|_| Vec::<IpAddr>::new(), |x| vec![x])
+            Some(
+                IpAddr::from_str(maybe_bracketed(&maybe_quoted(&ip)))
+                    .map_or_else(|_| Vec::<IpAddr>::new(), |x| vec![x]),
+            )
         }))
         .unify()
-        .or(warp::header("forwarded").map(|header: String| {
-            parse(&header)
-                .filter_map(|forward| match forward {
-                    Ok(Forwarded {
-                        forwarded_for:
-                            Some(NodeIdentifier {
-                                name: NodeName::Ip(ip),
+        .or(warp::any().map(|| None))
+        .unify()
+}
+
+/// Creates a `Filter` that extracts the ip addresses from the the "forwarded for" chain
+pub fn get_forwarded_for() -> impl Filter<Extract = (Vec<IpAddr>,), Error = Infallible> + Clone {
+    get_legacy_forwarded_for()
+        .and(
+            warp::header("forwarded")
+                .map(|header: String| {
+                    parse(&header)
+                        .filter_map(|forward| match forward {
+                            Ok(Forwarded {
+                                forwarded_for:
+                                    Some(NodeIdentifier {
+                                        name: NodeName::Ip(ip),
+                                        ..
+                                    }),
                                 ..
-                            }),
-                        ..
-                    }) => Some(ip),
-                    _ => None,
+                            }) => Some(ip),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>()
                 })
-                .collect::<Vec<_>>()
-        }))
-        .unify()
-        .or(warp::any().map(Vec::new))
-        .unify()
+                .or(warp::any().map(Vec::new))
+                .unify(),
+        )
+        .map(|legacy: Option<Vec<IpAddr>>, forwarded: Vec<IpAddr>| legacy.unwrap_or(forwarded))
 }
 
 enum CommaSeparatedIteratorState {
@@ -207,7 +418,7 @@ fn maybe_quoted(x: &str) -> Cow<str> {
     }
 }
 
-fn maybe_bracketed(x: &str) -> &str {
+pub(crate) fn maybe_bracketed(x: &str) -> &str {
     if x.as_bytes().first() == Some(&b'[') && x.as_bytes().last() == Some(&b']') {
         &x[1..x.len() - 1]
     } else {
@@ -0,0 +1,290 @@
+use crate::maybe_bracketed;
+use arc_swap::ArcSwap;
+use cidr::IpCidr;
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::lookup_host;
+use tokio::task::JoinHandle;
+
+/// A CIDR-aware set of trusted reverse proxies.
+///
+/// A bare [`IpAddr`] is treated as a single-host network (a `/32` for IPv4, a `/128` for IPv6),
+/// so existing `Vec<IpAddr>` allow-lists keep working unchanged. A set of excluded networks can
+/// be carved out of the included ones, even when an exclusion is a strict subset of a broader
+/// included range.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpCidr>,
+    excluded: Vec<IpCidr>,
+}
+
+impl TrustedProxies {
+    /// Returns whether `ip` falls inside one of the trusted networks and none of the excluded ones.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.networks.iter().any(|net| net.contains(ip))
+            && !self.excluded.iter().any(|net| net.contains(ip))
+    }
+
+    /// Loads a trusted-proxy set from a file, or from stdin when `path` is `-`.
+    ///
+    /// Each line is an ip or CIDR network, blank lines and `#` comments are ignored, and a line
+    /// prefixed with `!` excludes that entry, even when it falls inside a broader included range.
+    pub fn load_from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path == Path::new("-") {
+            Self::load_from_reader(io::stdin().lock())
+        } else {
+            Self::load_from_reader(BufReader::new(File::open(path)?))
+        }
+    }
+
+    fn load_from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut networks = Vec::new();
+        let mut excluded = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(entry) = line.strip_prefix('!') {
+                excluded.push(parse_network(entry.trim()).map_err(invalid_data)?);
+            } else {
+                networks.push(parse_network(line).map_err(invalid_data)?);
+            }
+        }
+        Ok(TrustedProxies { networks, excluded })
+    }
+
+    /// Resolves a set of hostnames (optionally `host:port`, the port is ignored) to their current
+    /// addresses using async DNS resolution, trusting every resolved address as a single host.
+    ///
+    /// A name that resolves to multiple `A`/`AAAA` records contributes all of them to the set. A
+    /// name that fails to resolve is logged and skipped, rather than discarding the names that
+    /// did resolve. If every hostname fails (e.g. a DNS outage), an error is returned instead of
+    /// an empty set, so callers such as [`spawn_refresh`](Self::spawn_refresh) can keep trusting
+    /// the previous set rather than replacing it with one that trusts nothing.
+    pub async fn resolve<I, S>(hostnames: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut networks = Vec::new();
+        let mut attempted = 0usize;
+        let mut failed = 0usize;
+        for hostname in hostnames {
+            attempted += 1;
+            let hostname = hostname.as_ref();
+            let target = if hostname.contains(':') {
+                Cow::Borrowed(hostname)
+            } else {
+                Cow::Owned(format!("{hostname}:0"))
+            };
+            match lookup_host(target.as_ref()).await {
+                Ok(addrs) => networks.extend(addrs.map(|addr| IpCidr::new_host(addr.ip()))),
+                Err(err) => {
+                    failed += 1;
+                    log::warn!("failed to resolve trusted proxy hostname {hostname}: {err}");
+                }
+            }
+        }
+        if attempted > 0 && failed == attempted {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("failed to resolve any of {attempted} trusted proxy hostname(s)"),
+            ));
+        }
+        Ok(networks.into())
+    }
+
+    /// Spawns a background task that re-resolves `hostnames` on `interval` and stores the
+    /// refreshed set in `shared`.
+    ///
+    /// A failed resolution is logged and leaves the previously stored set in place, rather than
+    /// clearing it.
+    pub fn spawn_refresh<I, S>(
+        shared: Arc<ArcSwap<TrustedProxies>>,
+        hostnames: I,
+        interval: Duration,
+    ) -> JoinHandle<()>
+    where
+        I: IntoIterator<Item = S> + Clone + Send + 'static,
+        I::IntoIter: Send,
+        S: AsRef<str> + Send,
+    {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match TrustedProxies::resolve(hostnames.clone()).await {
+                    Ok(resolved) => shared.store(Arc::new(resolved)),
+                    Err(err) => {
+                        log::warn!("failed to refresh trusted proxies, keeping previous set: {err}")
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl From<Vec<IpAddr>> for TrustedProxies {
+    fn from(ips: Vec<IpAddr>) -> Self {
+        ips.into_iter()
+            .map(IpCidr::new_host)
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+
+impl From<&Vec<IpAddr>> for TrustedProxies {
+    fn from(ips: &Vec<IpAddr>) -> Self {
+        ips.clone().into()
+    }
+}
+
+impl From<Vec<IpCidr>> for TrustedProxies {
+    fn from(networks: Vec<IpCidr>) -> Self {
+        TrustedProxies {
+            networks,
+            excluded: Vec::new(),
+        }
+    }
+}
+
+fn invalid_data(err: ParseNetworkError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Error returned when a [`TrustedProxies`] entry fails to parse.
+#[derive(Debug)]
+pub struct ParseNetworkError(String);
+
+impl fmt::Display for ParseNetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid trusted proxy entry: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseNetworkError {}
+
+/// Parses a single `TrustedProxies` entry, either a bare ip (`192.168.0.1`, `[fd00::1]`) or a
+/// CIDR network (`192.168.0.0/16`, `[fd00::]/8`).
+///
+/// Host bits set in the address (e.g. `10.9.9.9/24`) are masked off rather than rejected, since
+/// `IpCidr::new` only accepts strictly canonical networks and a single slightly-off line
+/// shouldn't abort loading the rest of the allow-list.
+fn parse_network(entry: &str) -> Result<IpCidr, ParseNetworkError> {
+    let (addr_part, len) = match entry.rsplit_once('/') {
+        Some((addr, len)) => (addr, Some(len)),
+        None => (entry, None),
+    };
+    let addr = IpAddr::from_str(maybe_bracketed(addr_part))
+        .map_err(|_| ParseNetworkError(entry.to_string()))?;
+    match len {
+        Some(len) => {
+            let len: u8 = len
+                .parse()
+                .map_err(|_| ParseNetworkError(entry.to_string()))?;
+            IpCidr::new(mask_host_bits(addr, len), len)
+                .map_err(|_| ParseNetworkError(entry.to_string()))
+        }
+        None => Ok(IpCidr::new_host(addr)),
+    }
+}
+
+/// Zeroes out the bits of `addr` past `len`, so a network length shorter than the address itself
+/// always yields a canonical (host-part-zero) network.
+fn mask_host_bits(addr: IpAddr, len: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let len = len.min(32);
+            let mask = u32::MAX.checked_shl(32 - len as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+        }
+        IpAddr::V6(addr) => {
+            let len = len.min(128);
+            let mask = u128::MAX.checked_shl(128 - len as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = ParseNetworkError;
+
+    /// Parses a comma-separated list of ips and/or CIDR networks, e.g. `"10.0.0.0/8, fd00::/8"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(parse_network)
+            .collect::<Result<Vec<_>, _>>()
+            .map(TrustedProxies::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_ip() {
+        let trusted: TrustedProxies = vec![IpAddr::from([10, 0, 0, 1])].into();
+        assert!(trusted.contains(&[10, 0, 0, 1].into()));
+        assert!(!trusted.contains(&[10, 0, 0, 2].into()));
+    }
+
+    #[test]
+    fn test_cidr_v4() {
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        assert!(trusted.contains(&[10, 1, 2, 3].into()));
+        assert!(!trusted.contains(&[11, 0, 0, 1].into()));
+    }
+
+    #[test]
+    fn test_cidr_v6() {
+        let trusted: TrustedProxies = "[fd00::]/8".parse().unwrap();
+        assert!(trusted.contains(&"fd00::1".parse().unwrap()));
+        assert!(!trusted.contains(&"fe00::1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_hostname() {
+        let trusted = TrustedProxies::resolve(["localhost:1234"]).await.unwrap();
+        assert!(
+            trusted.contains(&[127, 0, 0, 1].into()) || trusted.contains(&"::1".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_hostname_all_failed() {
+        let err = TrustedProxies::resolve(["this-host-should-not-exist.invalid:1234"])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_cidr_with_non_zero_host_bits() {
+        let trusted: TrustedProxies = "10.9.9.9/24".parse().unwrap();
+        assert!(trusted.contains(&[10, 9, 9, 1].into()));
+        assert!(!trusted.contains(&[10, 9, 10, 1].into()));
+    }
+
+    #[test]
+    fn test_load_from_reader_with_exclusions() {
+        let input = b"# comment\n\n10.0.0.0/8\n!10.9.9.9\nfd00::/8\n" as &[u8];
+        let trusted = TrustedProxies::load_from_reader(input).unwrap();
+        assert!(trusted.contains(&[10, 1, 2, 3].into()));
+        assert!(!trusted.contains(&[10, 9, 9, 9].into()));
+        assert!(trusted.contains(&"fd00::1".parse().unwrap()));
+        assert!(!trusted.contains(&[11, 0, 0, 1].into()));
+    }
+}
@@ -1,13 +1,17 @@
 use std::net::IpAddr;
 use warp::Filter;
-use warp_real_ip::real_ip;
+use warp_real_ip::{real_ip, real_ip_info, ForwardInfo};
 
 fn serve<'a>(trusted: Vec<IpAddr>) -> impl Filter<Extract = (String,)> + 'a {
     warp::any()
-        .and(real_ip((&trusted).into()))
+        .and(real_ip(trusted))
         .map(|addr: Option<IpAddr>| addr.unwrap().to_string())
 }
 
+fn serve_info<'a>(trusted: Vec<IpAddr>) -> impl Filter<Extract = (ForwardInfo,)> + 'a {
+    warp::any().and(real_ip_info(trusted))
+}
+
 #[tokio::test]
 async fn test_not_forwarded() {
     let remote: IpAddr = [1, 2, 3, 4].into();
@@ -83,3 +87,40 @@ async fn test_trusted_forwarded_no_for() {
         .await;
     assert_eq!(res.body(), "1.2.3.4");
 }
+
+#[tokio::test]
+async fn test_forward_info_from_forwarded_header() {
+    let remote: IpAddr = [1, 2, 3, 4].into();
+    let info = warp::test::request()
+        .remote_addr((remote, 80).into())
+        .header(
+            "forwarded",
+            "for=\"[2001:db8::1]:4711\";proto=https;host=example.com;by=9.9.9.9",
+        )
+        .filter(&serve_info(vec![remote]))
+        .await
+        .unwrap();
+    assert_eq!(info.client, Some("2001:db8::1".parse().unwrap()));
+    assert_eq!(info.port, Some(4711));
+    assert_eq!(info.proto, Some("https".to_string()));
+    assert_eq!(info.host, Some("example.com".to_string()));
+    assert_eq!(info.by, Some([9, 9, 9, 9].into()));
+}
+
+#[tokio::test]
+async fn test_forward_info_from_legacy_headers() {
+    let remote: IpAddr = [1, 2, 3, 4].into();
+    let info = warp::test::request()
+        .remote_addr((remote, 80).into())
+        .header("x-forwarded-for", "10.10.10.10")
+        .header("x-forwarded-proto", "https")
+        .header("x-forwarded-host", "example.com")
+        .header("x-forwarded-port", "8443")
+        .filter(&serve_info(vec![remote]))
+        .await
+        .unwrap();
+    assert_eq!(info.client, Some([10, 10, 10, 10].into()));
+    assert_eq!(info.proto, Some("https".to_string()));
+    assert_eq!(info.host, Some("example.com".to_string()));
+    assert_eq!(info.port, Some(8443));
+}